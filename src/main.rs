@@ -1,25 +1,31 @@
 //! Juju plugin for interacting with a bundle
 
 use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::io::ErrorKind as IoErrorKind;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use ex::fs;
 use failure::{format_err, Error};
 use petgraph::{
+    algo::toposort,
     dot::{Config as GraphConfig, Dot},
-    Graph,
+    Direction, Graph,
 };
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use rayon::ThreadPoolBuilder;
 use structopt::{self, clap::AppSettings, StructOpt};
 use tempfile::{NamedTempFile, TempDir};
+use walkdir::WalkDir;
 
 use juju::bundle::{Application, Bundle};
 use juju::charm_source::CharmSource;
 use juju::cmd::run;
 
+use serde::{Deserialize, Serialize};
+
 // Helper function for parsing `key=value` pairs passed in on the CLI
 fn parse_key_val(s: &str) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
     let pos = s.find('=');
@@ -74,6 +80,16 @@ struct BuildConfig {
     #[structopt(long = "serial")]
     #[structopt(help = "Build only one charm at a time")]
     serial: bool,
+
+    #[structopt(long = "build-plan")]
+    #[structopt(
+        help = "Print a JSON build plan describing each selected charm build (the build cache is not consulted), without building anything"
+    )]
+    build_plan: bool,
+
+    #[structopt(long = "force")]
+    #[structopt(help = "Ignore the build cache and rebuild every selected charm")]
+    force: bool,
 }
 
 /// CLI arguments for the `deploy` subcommand.
@@ -100,10 +116,26 @@ struct DeployConfig {
     #[structopt(help = "Build charmcraft charms with `--destructive-mode` flag")]
     destructive_mode: bool,
 
+    #[structopt(long = "build-plan")]
+    #[structopt(
+        help = "When used with --build, print a JSON build plan instead of building anything"
+    )]
+    build_plan: bool,
+
+    #[structopt(long = "force")]
+    #[structopt(help = "When used with --build, ignore the build cache and rebuild every selected charm")]
+    force: bool,
+
     #[structopt(long = "wait", default_value = "60")]
     #[structopt(help = "How long to wait in seconds for model to stabilize before deploying it")]
     wait: u32,
 
+    #[structopt(long = "ordered")]
+    #[structopt(
+        help = "Deploy applications in dependency waves derived from the bundle's relations, waiting for stability between each wave"
+    )]
+    ordered: bool,
+
     #[structopt(short = "a", long = "app")]
     #[structopt(help = "Select particular apps to deploy")]
     apps: Vec<String>,
@@ -170,6 +202,10 @@ struct ExportConfig {
     #[structopt(short = "o", long = "out")]
     #[structopt(help = "Where to write the exported bundle")]
     out: Option<String>,
+
+    #[structopt(long = "format", default_value = "dot")]
+    #[structopt(help = "Output format: `dot`, `json`, or `mermaid`")]
+    format: String,
 }
 
 /// CLI arguments for the `verify` subcommand.
@@ -178,6 +214,10 @@ struct VerifyConfig {
     #[structopt(short = "b", long = "bundle", default_value = "bundle.yaml")]
     #[structopt(help = "The bundle file to verify")]
     bundle: String,
+
+    #[structopt(long = "format", default_value = "text")]
+    #[structopt(help = "Output format for diagnostics: `text` or `json`")]
+    format: String,
 }
 
 /// Interact with a bundle and the charms contained therein.
@@ -222,6 +262,263 @@ enum Config {
     Verify(VerifyConfig),
 }
 
+/// One entry in a `--build-plan` dry-run, describing a single charm build
+/// invocation without actually performing it.
+#[derive(Serialize)]
+struct BuildPlanEntry {
+    app: String,
+    source: String,
+    charmcraft: bool,
+    destructive_mode: bool,
+    /// Where the built charm is expected to land, assuming the conventional
+    /// `{source}/{name}.charm` output layout. `Bundle::build` is the only
+    /// thing that knows the real path (it may differ for an unusual
+    /// charmcraft build step), so treat this as a best-effort approximation
+    /// rather than a guarantee.
+    output: String,
+}
+
+/// Resolve the source directory `app` would be built from, honoring a
+/// per-app `--app name=path` override from `build_apps` the same way
+/// `Bundle::build` does, and falling back to `app.source`'s own convention
+/// when no override was given for this app.
+fn resolve_build_source(
+    app: &Application,
+    name: &str,
+    bundle_path: &str,
+    build_apps: &Option<HashMap<String, Option<String>>>,
+) -> Option<PathBuf> {
+    if let Some(Some(path)) = build_apps.as_ref().and_then(|apps| apps.get(name)) {
+        return Some(PathBuf::from(path));
+    }
+
+    app.source(name, bundle_path)
+}
+
+/// Compute the build plan for `bundle` without building anything, mirroring
+/// the app selection and source resolution (including `--app name=path`
+/// overrides) that `build_with_cache` would otherwise perform.
+fn compute_build_plan(
+    bundle: &Bundle,
+    path: &str,
+    build_apps: &Option<HashMap<String, Option<String>>>,
+    destructive_mode: bool,
+) -> Vec<BuildPlanEntry> {
+    bundle
+        .applications
+        .iter()
+        .filter(|(name, _)| {
+            build_apps
+                .as_ref()
+                .map(|apps| apps.contains_key(*name))
+                .unwrap_or(true)
+        })
+        .filter_map(|(name, app)| {
+            let source = resolve_build_source(app, name, path, build_apps)?;
+            let charmcraft = source.join("charmcraft.yaml").exists();
+            // Best-effort approximation of `Bundle::build`'s output path; see
+            // the doc comment on `BuildPlanEntry::output`.
+            let output = source.join(format!("{}.charm", name));
+
+            Some(BuildPlanEntry {
+                app: name.clone(),
+                source: source.to_string_lossy().into_owned(),
+                charmcraft,
+                destructive_mode,
+                output: output.to_string_lossy().into_owned(),
+            })
+        })
+        .collect()
+}
+
+/// An on-disk cache of per-app build fingerprints, stored next to the
+/// bundle file, used to skip rebuilding charms whose sources haven't
+/// changed since the last build.
+#[derive(Serialize, Deserialize, Default)]
+struct BuildCache {
+    apps: HashMap<String, BuildCacheEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct BuildCacheEntry {
+    fingerprint: String,
+    output_charm_path: String,
+}
+
+impl BuildCache {
+    fn path_for(bundle_path: &str) -> PathBuf {
+        PathBuf::from(bundle_path).with_file_name(".juju-bundle-build-cache.json")
+    }
+
+    fn load(bundle_path: &str) -> Self {
+        fs::read(Self::path_for(bundle_path))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, bundle_path: &str) -> Result<(), Error> {
+        fs::write(
+            Self::path_for(bundle_path),
+            serde_json::to_vec_pretty(self)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Compute a fingerprint for a charm's source directory, combined with the
+/// build inputs that affect the resulting artifact (`destructive_mode` and
+/// the charmcraft.yaml contents, which also distinguishes charmcraft charms
+/// from legacy ones). Falls back to path + mtime + size when a source file
+/// can't be read.
+///
+/// Any built charm landing inside `source` is excluded from the walk, since
+/// hashing it would make the fingerprint change the moment a build produces
+/// it, defeating the cache on the very next run. We exclude both the
+/// conventional `{source}/{name}.charm` name and `known_output` (the actual
+/// `output_charm_path` recorded by a previous build in the cache), since
+/// charmcraft typically names its artifact `{name}_{base}.charm` rather
+/// than the conventional name.
+fn fingerprint_app(
+    source: &Path,
+    name: &str,
+    known_output: Option<&Path>,
+    destructive_mode: bool,
+) -> Result<String, Error> {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let output_charm = source.join(format!("{}.charm", name));
+
+    let mut files: Vec<PathBuf> = WalkDir::new(source)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|file| *file != output_charm && Some(file.as_path()) != known_output)
+        .collect();
+    files.sort();
+
+    for file in &files {
+        file.strip_prefix(source)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .hash(&mut hasher);
+
+        match fs::read(file) {
+            Ok(contents) => contents.hash(&mut hasher),
+            Err(_) => {
+                if let Ok(metadata) = file.metadata() {
+                    metadata.len().hash(&mut hasher);
+                    if let Ok(modified) = metadata.modified() {
+                        modified.hash(&mut hasher);
+                    }
+                }
+            }
+        }
+    }
+
+    destructive_mode.hash(&mut hasher);
+    if let Ok(contents) = fs::read(source.join("charmcraft.yaml")) {
+        contents.hash(&mut hasher);
+    }
+
+    Ok(format!("{:x}", hasher.finish()))
+}
+
+/// Build `bundle`, skipping any selected app whose fingerprint matches the
+/// cache from a previous build and whose cached output artifact still
+/// exists on disk. Skipped apps are pointed at their cached charm, the same
+/// way `Bundle::build` itself repoints a built app away from its source.
+fn build_with_cache(
+    bundle: &mut Bundle,
+    bundle_path: &str,
+    build_apps: Option<HashMap<String, Option<String>>>,
+    destructive_mode: bool,
+    parallel: bool,
+    force: bool,
+) -> Result<(), Error> {
+    let mut cache = if force {
+        BuildCache::default()
+    } else {
+        BuildCache::load(bundle_path)
+    };
+
+    let candidates: Vec<String> = bundle
+        .applications
+        .iter()
+        .filter(|(name, _)| {
+            build_apps
+                .as_ref()
+                .map(|apps| apps.contains_key(*name))
+                .unwrap_or(true)
+        })
+        .filter_map(|(name, app)| {
+            resolve_build_source(app, name, bundle_path, &build_apps).map(|_| name.clone())
+        })
+        .collect();
+
+    let mut fingerprints: HashMap<String, String> = HashMap::new();
+    let mut rebuild_set: HashMap<String, Option<String>> = HashMap::new();
+
+    for name in &candidates {
+        let app = &bundle.applications[name];
+        let source = resolve_build_source(app, name, bundle_path, &build_apps)
+            .expect("candidate apps are known to have a source");
+        let known_output = cache
+            .apps
+            .get(name)
+            .map(|entry| Path::new(&entry.output_charm_path));
+        let fingerprint = fingerprint_app(&source, name, known_output, destructive_mode)?;
+
+        let up_to_date = cache.apps.get(name).map_or(false, |entry| {
+            entry.fingerprint == fingerprint && Path::new(&entry.output_charm_path).exists()
+        });
+
+        if up_to_date {
+            println!("Skipping {} (unchanged since last build)", name);
+
+            let output_charm_path = cache.apps[name].output_charm_path.clone();
+            let app = bundle
+                .applications
+                .get_mut(name)
+                .expect("name comes from bundle.applications");
+            app.charm = Some(output_charm_path);
+            app.source = None;
+        } else {
+            fingerprints.insert(name.clone(), fingerprint);
+            let requested = build_apps
+                .as_ref()
+                .and_then(|apps| apps.get(name).cloned())
+                .flatten();
+            rebuild_set.insert(name.clone(), requested);
+        }
+    }
+
+    if !rebuild_set.is_empty() {
+        bundle.build(bundle_path, Some(rebuild_set.clone()), destructive_mode, parallel)?;
+
+        for name in rebuild_set.keys() {
+            if let Some(output) = bundle
+                .applications
+                .get(name)
+                .and_then(|app| app.charm.clone())
+            {
+                cache.apps.insert(
+                    name.clone(),
+                    BuildCacheEntry {
+                        fingerprint: fingerprints[name].clone(),
+                        output_charm_path: output,
+                    },
+                );
+            }
+        }
+    }
+
+    cache.save(bundle_path)?;
+
+    Ok(())
+}
+
 /// Run `build` subcommand
 fn build(c: BuildConfig) -> Result<(), Error> {
     println!("Building bundle from {}", c.bundle);
@@ -238,7 +535,20 @@ fn build(c: BuildConfig) -> Result<(), Error> {
         Some(apps)
     };
 
-    bundle.build(&c.bundle, build_apps, c.destructive_mode, !c.serial)?;
+    if c.build_plan {
+        let plan = compute_build_plan(&bundle, &c.bundle, &build_apps, c.destructive_mode);
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+
+    build_with_cache(
+        &mut bundle,
+        &c.bundle,
+        build_apps,
+        c.destructive_mode,
+        !c.serial,
+        c.force,
+    )?;
 
     bundle.save(&c.output_bundle)?;
 
@@ -247,6 +557,60 @@ fn build(c: BuildConfig) -> Result<(), Error> {
     Ok(())
 }
 
+/// Group `bundle`'s applications into dependency waves, using the same
+/// provider/requirer split as `export`. Each wave can be deployed in
+/// parallel; a wave only depends on applications in earlier waves. If the
+/// relation graph contains a cycle, all applications are put into a single
+/// wave, with a warning, rather than treated as an error.
+fn compute_waves(bundle: &Bundle) -> Vec<Vec<String>> {
+    let mut graph = Graph::<&str, ()>::new();
+    let mut index_of: HashMap<&str, petgraph::graph::NodeIndex> = HashMap::new();
+
+    for name in bundle.applications.keys() {
+        index_of.insert(name.as_str(), graph.add_node(name.as_str()));
+    }
+
+    for rel in &bundle.relations {
+        let provider = rel[0].split(':').next().unwrap_or(&rel[0]);
+        let requirer = rel[1].split(':').next().unwrap_or(&rel[1]);
+
+        if let (Some(&from), Some(&to)) = (index_of.get(provider), index_of.get(requirer)) {
+            graph.add_edge(from, to, ());
+        }
+    }
+
+    match toposort(&graph, None) {
+        Ok(order) => {
+            let mut wave_of = HashMap::new();
+
+            for idx in order {
+                let wave = graph
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .map(|pred| wave_of.get(&pred).copied().unwrap_or(0) + 1)
+                    .max()
+                    .unwrap_or(0);
+                wave_of.insert(idx, wave);
+            }
+
+            let wave_count = wave_of.values().copied().max().unwrap_or(0) + 1;
+            let mut waves = vec![Vec::new(); wave_count];
+            for (idx, wave) in wave_of {
+                waves[wave].push(graph[idx].to_string());
+            }
+
+            waves
+        }
+        Err(cycle) => {
+            println!(
+                "Warning: the relation graph contains a cycle (found at {}); deploying all applications in a single wave.",
+                graph[cycle.node_id()]
+            );
+
+            vec![bundle.applications.keys().cloned().collect()]
+        }
+    }
+}
+
 /// Run `deploy` subcommand
 fn deploy(c: DeployConfig) -> Result<(), Error> {
     println!("Building and deploying bundle from {}", c.bundle_path);
@@ -270,7 +634,21 @@ fn deploy(c: DeployConfig) -> Result<(), Error> {
             ensure_subset(&to_build, &existing)?;
             Some(apps)
         };
-        bundle.build(&c.bundle_path, build_apps, c.destructive_mode, !c.serial)?;
+
+        if c.build_plan {
+            let plan = compute_build_plan(&bundle, &c.bundle_path, &build_apps, c.destructive_mode);
+            println!("{}", serde_json::to_string_pretty(&plan)?);
+            return Ok(());
+        }
+
+        build_with_cache(
+            &mut bundle,
+            &c.bundle_path,
+            build_apps,
+            c.destructive_mode,
+            !c.serial,
+            c.force,
+        )?;
     }
 
     // If we're only upgrading charms, we can skip the rest of the logic
@@ -306,6 +684,101 @@ fn deploy(c: DeployConfig) -> Result<(), Error> {
         }
     }
 
+    if c.ordered {
+        let waves = compute_waves(&bundle);
+        let wave_count = waves.len();
+
+        let wave_of: HashMap<String, usize> = waves
+            .iter()
+            .enumerate()
+            .flat_map(|(i, wave)| wave.iter().map(move |name| (name.clone(), i)))
+            .collect();
+
+        // `limit_apps` drops a relation unless both endpoints are in the
+        // wave being deployed, and every dependency edge crosses from an
+        // earlier wave's provider to a later wave's requirer, so none of
+        // them would survive being deployed alongside either wave. Instead,
+        // relate each cross-wave pair with `juju add-relation` as soon as
+        // the later of its two waves is up and before we wait for that wave
+        // to stabilize, so a requirer blocked on the relation actually has
+        // it by the time we wait.
+        let mut pending_relations: Vec<Vec<Vec<String>>> = vec![Vec::new(); wave_count];
+        for rel in &bundle.relations {
+            let provider = rel[0].split(':').next().unwrap_or(&rel[0]);
+            let requirer = rel[1].split(':').next().unwrap_or(&rel[1]);
+
+            if let (Some(&wp), Some(&wr)) = (wave_of.get(provider), wave_of.get(requirer)) {
+                if wp != wr {
+                    pending_relations[wp.max(wr)].push(rel.clone());
+                }
+            }
+        }
+
+        for (i, wave) in waves.into_iter().enumerate() {
+            println!(
+                "\n\nDeploying wave {}/{}: {}",
+                i + 1,
+                wave_count,
+                wave.join(", ")
+            );
+
+            let mut wave_bundle = bundle.clone();
+            wave_bundle.limit_apps(&wave[..], &[])?;
+
+            let wave_temp = NamedTempFile::new()?;
+            wave_bundle.save(wave_temp.path())?;
+
+            let exit_status = Command::new("juju")
+                .args(&["deploy", &wave_temp.path().to_string_lossy()])
+                .args(c.deploy_args.clone())
+                .spawn()?
+                .wait()?;
+
+            if !exit_status.success() {
+                return Err(format_err!(
+                    "Encountered an error while deploying wave {}: {}",
+                    i + 1,
+                    exit_status.to_string()
+                ));
+            }
+
+            for rel in &pending_relations[i] {
+                println!("\n\nRelating {} to {}.", rel[0], rel[1]);
+
+                let exit_status = Command::new("juju")
+                    .args(&["add-relation", rel[0].as_str(), rel[1].as_str()])
+                    .spawn()?
+                    .wait()?;
+
+                if !exit_status.success() {
+                    return Err(format_err!(
+                        "Encountered an error while relating {} and {}: {}",
+                        rel[0],
+                        rel[1],
+                        exit_status.to_string()
+                    ));
+                }
+            }
+
+            println!("\n\nWaiting for wave {} to stabilize.", i + 1);
+
+            let exit_status = Command::new("juju")
+                .args(&["wait", "-wv", "-t", &c.wait.to_string()])
+                .spawn()?
+                .wait()?;
+
+            if !exit_status.success() {
+                return Err(format_err!(
+                    "Encountered an error while waiting for wave {} to stabilize: {}",
+                    i + 1,
+                    exit_status.to_string()
+                ));
+            }
+        }
+
+        return Ok(());
+    }
+
     println!("\n\nDeploying bundle");
 
     let exit_status = Command::new("juju")
@@ -356,20 +829,84 @@ fn publish(c: PublishConfig) -> Result<(), Error> {
         ThreadPoolBuilder::new().num_threads(1).build_global()?;
     }
 
+    // Render one bar per in-flight charm when attached to a terminal, and
+    // fall back to plain line-buffered logging in CI so logs stay readable.
+    let is_tty = atty::is(atty::Stream::Stdout);
+    let multi_progress = MultiProgress::new();
+    let bar_style = ProgressStyle::default_spinner()
+        .template("{spinner:.green} {prefix:.bold} {msg} ({elapsed})")
+        .unwrap_or_else(|_| ProgressStyle::default_spinner());
+
+    let total = bundle.applications.len();
+
+    // A persistent summary bar above the per-charm bars, tracking overall
+    // completed/total progress since the per-charm bars each disappear into
+    // "done" independently.
+    let summary_bar = if is_tty {
+        multi_progress.add(ProgressBar::new(total as u64))
+    } else {
+        ProgressBar::hidden()
+    };
+    summary_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{bar:40.cyan/blue} {pos}/{len} charms published ({elapsed})")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+
+    let bars: HashMap<&String, ProgressBar> = bundle
+        .applications
+        .keys()
+        .map(|name| {
+            let bar = if is_tty {
+                multi_progress.add(ProgressBar::new_spinner())
+            } else {
+                ProgressBar::hidden()
+            };
+            bar.set_style(bar_style.clone());
+            bar.set_prefix(name.clone());
+            bar.set_message("waiting");
+            if is_tty {
+                bar.enable_steady_tick(std::time::Duration::from_millis(120));
+            }
+            (name, bar)
+        })
+        .collect();
+
+    let completed = std::sync::atomic::AtomicUsize::new(0);
+
     // Ensure each charm is built and uploaded to each channel
     bundle.applications.par_iter().try_for_each(
         |(name, app): (&String, &Application)| -> Result<(), Error> {
+            let bar = &bars[name];
+
             if app.source(name, path).is_some() {
+                bar.set_message("building/uploading");
+                if !is_tty {
+                    println!("[{}] building/uploading", name);
+                }
                 app.upload_charmhub(name, path, &c.release_to, c.destructive_mode)?;
             }
             if c.prune {
+                bar.set_message("pruning");
+                if !is_tty {
+                    println!("[{}] pruning", name);
+                }
                 run("docker", &["system", "prune", "-af"])?;
             }
 
+            let done = completed.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            bar.finish_with_message("done");
+            summary_bar.set_position(done as u64);
+            if !is_tty {
+                println!("[{}/{}] {} done", done, total, name);
+            }
+
             Ok(())
         },
     )?;
 
+    summary_bar.finish_with_message("done");
+
     for channel in &c.release_to {
         // Make a copy of the bundle with exact revisions of each charm
         let mut new_bundle = bundle.clone();
@@ -410,47 +947,229 @@ fn publish(c: PublishConfig) -> Result<(), Error> {
     Ok(())
 }
 
-/// Run `export` subcommand
-fn export(c: ExportConfig) -> Result<(), Error> {
-    let bundle = Bundle::load(&c.bundle)?;
+/// Intermediate representation of a bundle's relation topology, shared by
+/// the `dot`, `json`, and `mermaid` export serializers below.
+struct ExportGraph {
+    apps: Vec<String>,
+    edges: Vec<(String, String, String)>,
+}
+
+/// Derive the shared topology from `bundle`, splitting each relation
+/// endpoint on `:` the same way the original Graphviz export did.
+fn build_export_graph(bundle: &Bundle) -> ExportGraph {
+    let apps = bundle.applications.keys().cloned().collect();
+
+    let edges = bundle
+        .relations
+        .iter()
+        .map(|rel| {
+            let app_a = rel[0].split(':').next().unwrap_or(&rel[0]).to_string();
+            let app_b = rel[1].split(':').next().unwrap_or(&rel[1]).to_string();
+            let rel_name = rel[0].split(':').last().unwrap_or("").to_string();
+
+            (app_a, app_b, rel_name)
+        })
+        .collect();
+
+    ExportGraph { apps, edges }
+}
+
+fn export_dot(graph: &ExportGraph) -> String {
+    let mut pg = Graph::<_, String>::new();
+    let mut index_of = HashMap::new();
+
+    for app in &graph.apps {
+        index_of.insert(app, pg.add_node(app));
+    }
+    for (from, to, rel_name) in &graph.edges {
+        pg.add_edge(index_of[from], index_of[to], rel_name.clone());
+    }
+
+    format!("{}", Dot::with_config(&pg, &[GraphConfig::EdgeNoLabel]))
+}
+
+#[derive(Serialize)]
+struct ExportJsonEdge {
+    from: String,
+    to: String,
+    relation: String,
+}
+
+#[derive(Serialize)]
+struct ExportJson {
+    nodes: Vec<String>,
+    edges: Vec<ExportJsonEdge>,
+}
+
+fn export_json(graph: &ExportGraph) -> Result<String, Error> {
+    let doc = ExportJson {
+        nodes: graph.apps.clone(),
+        edges: graph
+            .edges
+            .iter()
+            .map(|(from, to, relation)| ExportJsonEdge {
+                from: from.clone(),
+                to: to.clone(),
+                relation: relation.clone(),
+            })
+            .collect(),
+    };
+
+    Ok(serde_json::to_string_pretty(&doc)?)
+}
 
-    let mut graph = Graph::<_, String>::new();
+/// Mermaid has no generic escaping mechanism for node/edge text, but
+/// quoting node names and replacing `|` in edge labels with `/` keeps names with
+/// spaces or punctuation from breaking the diagram syntax.
+fn mermaid_quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "#quot;"))
+}
 
-    for app in bundle.applications.keys() {
-        graph.add_node(app);
+fn export_mermaid(graph: &ExportGraph) -> String {
+    let mut output = String::from("graph LR\n");
+
+    let mut connected: HashSet<&String> = HashSet::new();
+    for (from, to, relation) in &graph.edges {
+        connected.insert(from);
+        connected.insert(to);
+        output.push_str(&format!(
+            "    {}-->|{}|{}\n",
+            mermaid_quote(from),
+            relation.replace('|', "/"),
+            mermaid_quote(to)
+        ));
     }
-    for rel in bundle.relations {
-        let app_a = rel[0].split(':').next().unwrap_or(&rel[0]);
-        let app_b = rel[1].split(':').next().unwrap_or(&rel[1]);
-        let rel_name = rel[0].split(':').last().unwrap_or("");
-        let index_a = graph.node_indices().find(|i| graph[*i] == app_a).unwrap();
-        let index_b = graph.node_indices().find(|i| graph[*i] == app_b).unwrap();
-        graph.add_edge(index_a, index_b, rel_name.to_string());
+
+    // Apps with no relations have no edge line to appear in, so declare them
+    // standalone, the same way `dot` and `json` include every app regardless
+    // of whether it's related to anything.
+    for app in &graph.apps {
+        if !connected.contains(app) {
+            output.push_str(&format!("    {}\n", mermaid_quote(app)));
+        }
     }
-    let output = Dot::with_config(&graph, &[GraphConfig::EdgeNoLabel]);
+
+    output
+}
+
+/// Run `export` subcommand
+fn export(c: ExportConfig) -> Result<(), Error> {
+    let bundle = Bundle::load(&c.bundle)?;
+    let graph = build_export_graph(&bundle);
+
+    let output = match c.format.as_str() {
+        "dot" => export_dot(&graph),
+        "json" => export_json(&graph)?,
+        "mermaid" => export_mermaid(&graph),
+        other => {
+            return Err(format_err!(
+                "Unknown export format `{}`; expected `dot`, `json`, or `mermaid`",
+                other
+            ))
+        }
+    };
 
     match c.out {
-        Some(out) => fs::write(out, format!("{}", output))?,
+        Some(out) => fs::write(out, output)?,
         None => println!("{}", output),
     }
 
     Ok(())
 }
 
+/// One structured diagnostic produced by `verify --format json`.
+#[derive(Serialize)]
+struct VerifyDiagnostic {
+    app: String,
+    source: Option<String>,
+    severity: String,
+    message: String,
+    code: String,
+}
+
 /// Run `verify` subcommand
 fn verify(c: VerifyConfig) -> Result<(), Error> {
     let bundle = Bundle::load(&c.bundle)?;
-    println!("Checking {}", c.bundle);
 
-    for (name, app) in bundle.applications {
-        if let Some(source) = app.source(&name, &c.bundle) {
-            if let Err(err) = CharmSource::load(source) {
-                println!("Error for charm {}: {}", name, err);
+    if c.format != "json" {
+        println!("Checking {}", c.bundle);
+    }
+
+    let mut diagnostics = Vec::new();
+
+    for (name, app) in &bundle.applications {
+        match app.source(name, &c.bundle) {
+            Some(source) => {
+                if let Err(err) = CharmSource::load(&source) {
+                    diagnostics.push(VerifyDiagnostic {
+                        app: name.clone(),
+                        source: Some(source.to_string_lossy().into_owned()),
+                        severity: "error".to_string(),
+                        message: err.to_string(),
+                        code: "charm-source-load-failed".to_string(),
+                    });
+                }
+            }
+            None => {
+                if app.charm.is_none() && app.channel.is_none() {
+                    diagnostics.push(VerifyDiagnostic {
+                        app: name.clone(),
+                        source: None,
+                        severity: "error".to_string(),
+                        message: format!(
+                            "Application `{}` has neither a `source` nor a `charm`/`channel` to deploy from",
+                            name
+                        ),
+                        code: "app-missing-charm-reference".to_string(),
+                    });
+                }
             }
         }
     }
 
-    Ok(())
+    // Reuse the `ensure_subset`-style check: every relation endpoint must
+    // name an application that's actually in the bundle.
+    for rel in &bundle.relations {
+        for endpoint in rel {
+            let endpoint_app = endpoint.split(':').next().unwrap_or(endpoint).to_string();
+
+            if !bundle.applications.contains_key(&endpoint_app) {
+                diagnostics.push(VerifyDiagnostic {
+                    app: endpoint_app.clone(),
+                    source: None,
+                    severity: "error".to_string(),
+                    message: format!(
+                        "Relation endpoint `{}` references application `{}`, which is not in bundle.applications",
+                        endpoint, endpoint_app
+                    ),
+                    code: "relation-endpoint-unknown-app".to_string(),
+                });
+            }
+        }
+    }
+
+    let error_count = diagnostics
+        .iter()
+        .filter(|d| d.severity == "error")
+        .count();
+
+    if c.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+    } else {
+        for d in &diagnostics {
+            println!("{} [{}] {}: {}", d.severity, d.code, d.app, d.message);
+        }
+    }
+
+    if error_count > 0 {
+        Err(format_err!(
+            "Verification of {} failed with {} error(s)",
+            c.bundle,
+            error_count
+        ))
+    } else {
+        Ok(())
+    }
 }
 
 fn main() -> Result<(), Error> {